@@ -1,43 +1,65 @@
+use std::collections::BTreeMap;
 use std::ops::Range;
 
 use crate::biome::Biome;
 use crate::complete::section::{Section, SectionBlockIter};
+use crate::light::ChunkLight;
 use crate::pre13::Pre13Section;
 use crate::pre18::Pre18Section;
 use crate::{java, Block};
 
+/// A sparse, Y-keyed store of a chunk's sections.
+///
+/// Sections are keyed by their real section-Y index, the same way Minecraft
+/// itself addresses sections in the 1.18+ chunk format, rather than by a
+/// dense `Vec` offset. This means a vertical slice with no stored section --
+/// common in superflat/void worlds and in partially generated chunks -- is
+/// simply absent from the map instead of requiring every index in `0..len`
+/// to exist.
 pub struct SectionTower {
-    sections: Vec<Section>,
+    sections: BTreeMap<i8, Section>,
 
     y_min: isize,
     y_max: isize,
+
+    /// Set by [`set_block`][`Self::set_block`]/[`set_biome`][`Self::set_biome`]
+    /// to mark that this tower has been edited; only
+    /// [`clear_dirty`][`Self::clear_dirty`] resets it.
+    dirty: bool,
 }
 
 impl SectionTower {
     pub fn block(&self, x: usize, y: isize, z: usize) -> Option<&Block> {
-        let section_index = self.y_to_index(y);
-
-        let section = self.sections.get(section_index).unwrap();
+        let section = self.sections.get(&Self::y_to_section_y(y))?;
+        let section_y = y.rem_euclid(16) as usize;
 
-        //first compute current section y then sub that from the ask y to get the y in the section
-        let section_y = y - ((16 * section_index) as isize + self.y_min);
-
-        section.block(x, section_y as usize, z)
+        section.block(x, section_y, z)
     }
 
     pub fn biome(&self, x: usize, y: isize, z: usize) -> Option<Biome> {
-        let section_index = self.y_to_index(y);
+        let section = self.sections.get(&Self::y_to_section_y(y))?;
+        let section_y = y.rem_euclid(16) as usize;
 
-        let section = self.sections.get(section_index).unwrap();
+        section.biome(x, section_y, z)
+    }
 
-        //first compute current section y then sub that from the ask y to get the y in the section
-        let section_y = y - ((16 * section_index) as isize + self.y_min);
+    /// The stored `(block light, sky light)` at this position, if the
+    /// section is present and has light arrays recorded. `None` means the
+    /// caller should fall back to a computed estimate, e.g.
+    /// [`fallback_sky_light`][`crate::light::fallback_sky_light`].
+    pub fn light(&self, x: usize, y: isize, z: usize) -> Option<(u8, u8)> {
+        let section = self.sections.get(&Self::y_to_section_y(y))?;
+        let section_y = y.rem_euclid(16) as usize;
 
-        section.biome(x, section_y as usize, z)
+        let block = section.block_light(x, section_y, z)?;
+        let sky = section.sky_light(x, section_y, z)?;
+
+        Some((block, sky))
     }
 
-    fn y_to_index(&self, y: isize) -> usize {
-        ((y - self.y_min) / 16) as usize
+    /// The section-Y that a world Y coordinate falls within.
+    fn y_to_section_y(y: isize) -> i8 {
+        y.div_euclid(16) as i8
     }
 
     pub fn y_range(&self) -> Range<isize> {
@@ -47,18 +69,94 @@ impl SectionTower {
     pub fn iter_blocks(&self) -> SectionTowerBlockIter {
         SectionTowerBlockIter::new(self)
     }
+
+    /// Set the block at `(x, y, z)`, creating the target section on demand
+    /// if it wasn't already present. The section grows its palette (and
+    /// widens its packed bits-per-entry if needed) to fit the new block.
+    pub fn set_block(&mut self, x: usize, y: isize, z: usize, block: Block) {
+        let section_y = Self::y_to_section_y(y);
+        let local_y = y.rem_euclid(16) as usize;
+
+        self.sections
+            .entry(section_y)
+            .or_insert_with(Section::empty)
+            .set_block(x, local_y, z, block);
+
+        self.dirty = true;
+    }
+
+    /// Set the biome at `(x, y, z)`, creating the target section on demand
+    /// if it wasn't already present.
+    pub fn set_biome(&mut self, x: usize, y: isize, z: usize, biome: Biome) {
+        let section_y = Self::y_to_section_y(y);
+        let local_y = y.rem_euclid(16) as usize;
+
+        self.sections
+            .entry(section_y)
+            .or_insert_with(Section::empty)
+            .set_biome(x, local_y, z, biome);
+
+        self.dirty = true;
+    }
+
+    /// Whether this tower has been edited via [`set_block`][`Self::set_block`]
+    /// or [`set_biome`][`Self::set_biome`] since it was built or since
+    /// [`clear_dirty`][`Self::clear_dirty`] was last called. Serializing the
+    /// tower does not clear it.
+    pub fn dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Clear the dirty flag, typically once the tower has been re-serialized
+    /// and written back into its region.
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Iterate over this tower's present sections, keyed by section-Y. Used
+    /// by [`crate::complete::serialize`] to rebuild each section's on-disk
+    /// NBT from its current block contents.
+    pub(crate) fn sections(&self) -> impl Iterator<Item = (i8, &Section)> {
+        self.sections.iter().map(|(&y, section)| (y, section))
+    }
+
+    /// Build a tower directly from its sections, bypassing the `From`
+    /// conversions that otherwise construct one. Only exists so other
+    /// modules' tests (e.g. [`crate::lighting`]) can exercise behaviour that
+    /// depends on a populated `SectionTower` without a real chunk to convert.
+    #[cfg(test)]
+    pub(crate) fn for_test(sections: BTreeMap<i8, Section>, y_min: isize, y_max: isize) -> Self {
+        Self {
+            sections,
+            y_min,
+            y_max,
+            dirty: false,
+        }
+    }
+}
+
+impl ChunkLight for SectionTower {
+    fn light(&self, x: usize, y: isize, z: usize) -> Option<(u8, u8)> {
+        SectionTower::light(self, x, y, z)
+    }
 }
 
 impl From<java::SectionTower<java::Section>> for SectionTower {
     fn from(current_tower: java::SectionTower<java::Section>) -> Self {
+        let y_min = current_tower.y_min();
+        let y_max = current_tower.y_max();
+        let y_min_section = y_min.div_euclid(16) as i8;
+
         let mut tower = SectionTower {
-            sections: vec![],
-            y_min: current_tower.y_min(),
-            y_max: current_tower.y_max(),
+            sections: BTreeMap::new(),
+            y_min,
+            y_max,
+            dirty: false,
         };
 
-        for section in current_tower.take_sections() {
-            tower.sections.push(section.into())
+        for (index, section) in current_tower.take_sections().into_iter().enumerate() {
+            let section_y = y_min_section + index as i8;
+            tower.sections.insert(section_y, section.into());
         }
 
         tower
@@ -69,29 +167,37 @@ impl From<(java::SectionTower<Pre18Section>, Vec<Biome>)> for SectionTower {
     fn from(
         (current_tower, current_biomes): (java::SectionTower<Pre18Section>, Vec<Biome>),
     ) -> Self {
+        let y_min = current_tower.y_min();
+        let y_max = current_tower.y_max();
+        let y_min_section = y_min.div_euclid(16) as i8;
+        let y_max_section = y_max.div_euclid(16) as i8;
+
         let mut tower = SectionTower {
-            sections: vec![],
-            y_min: current_tower.y_min(),
-            y_max: current_tower.y_max(),
+            sections: BTreeMap::new(),
+            y_min,
+            y_max,
+            dirty: false,
         };
 
         const BIOME_COUNT: usize = 4 * 4 * 4;
 
-        //needed to skip first because it seems like there is a sections to much in the list
-        // could be connected to java::section_tower.get_section_for_y -> todo
-        for (index, section) in current_tower
-            .take_sections()
-            .into_iter()
-            .enumerate()
-            .skip(1)
-        {
-            tower.sections.push(
-                (
-                    section,
-                    &current_biomes[((index - 1) * BIOME_COUNT)..(index * BIOME_COUNT)],
-                )
-                    .into(),
-            );
+        // The on-disk section list carries one extra section below y_min
+        // that exists only to hold lighting data for the space below the
+        // world; it has no block/biome content of its own. Rather than
+        // hard-coding a `.skip(1)` to drop it, derive each section's real
+        // Y and only keep the ones that actually fall in range -- this
+        // also holds if a future format adds padding at the top instead.
+        let mut biome_index = 0;
+        for (raw_index, section) in current_tower.take_sections().into_iter().enumerate() {
+            let section_y = y_min_section + raw_index as i8 - 1;
+            if section_y < y_min_section || section_y >= y_max_section {
+                continue;
+            }
+
+            let biomes =
+                &current_biomes[(biome_index * BIOME_COUNT)..((biome_index + 1) * BIOME_COUNT)];
+            tower.sections.insert(section_y, (section, biomes).into());
+            biome_index += 1;
         }
 
         tower
@@ -106,17 +212,24 @@ impl From<(java::SectionTower<Pre13Section>, Vec<Block>, Vec<Biome>)> for Sectio
             Vec<Biome>,
         ),
     ) -> Self {
+        let y_min = current_tower.y_min();
+        let y_max = current_tower.y_max();
+        let y_min_section = y_min.div_euclid(16) as i8;
+
         let mut tower = SectionTower {
-            sections: vec![],
-            y_min: current_tower.y_min(),
-            y_max: current_tower.y_max(),
+            sections: BTreeMap::new(),
+            y_min,
+            y_max,
+            dirty: false,
         };
 
         const BIOME_COUNT: usize = 4 * 4 * 4;
         const BLOCK_COUNT: usize = 16 * 16 * 16;
 
         for (index, _section) in current_tower.take_sections().into_iter().enumerate() {
-            tower.sections.push(
+            let section_y = y_min_section + index as i8;
+            tower.sections.insert(
+                section_y,
                 (
                     &current_blocks[(index * BLOCK_COUNT)..((index + 1) * BLOCK_COUNT)],
                     &current_biomes[(index * BIOME_COUNT)..((index + 1) * BIOME_COUNT)],
@@ -130,18 +243,18 @@ impl From<(java::SectionTower<Pre13Section>, Vec<Block>, Vec<Biome>)> for Sectio
 }
 
 pub struct SectionTowerBlockIter<'a> {
-    sections: &'a Vec<Section>,
-
-    section_index_current: usize,
-    section_iter_current: SectionBlockIter<'a>,
+    sections: std::collections::btree_map::Values<'a, i8, Section>,
+    section_iter_current: Option<SectionBlockIter<'a>>,
 }
 
 impl<'a> SectionTowerBlockIter<'a> {
     pub fn new(section_tower: &'a SectionTower) -> Self {
+        let mut sections = section_tower.sections.values();
+        let section_iter_current = sections.next().map(|section| section.iter_blocks());
+
         Self {
-            sections: &section_tower.sections,
-            section_iter_current: section_tower.sections.get(0).unwrap().iter_blocks(),
-            section_index_current: 0,
+            sections,
+            section_iter_current,
         }
     }
 }
@@ -150,23 +263,68 @@ impl<'a> Iterator for SectionTowerBlockIter<'a> {
     type Item = &'a Block;
 
     fn next(&mut self) -> Option<Self::Item> {
-        return match self.section_iter_current.next() {
-            None => {
-                //check if it was the last section
-                if self.section_index_current >= self.sections.len() - 1 {
-                    return None;
+        loop {
+            let current = self.section_iter_current.as_mut()?;
+
+            match current.next() {
+                Some(block) => return Some(block),
+                // Sections are only present for stored Ys, so the next
+                // iterator (if any) may belong to a non-adjacent section-Y;
+                // that's fine, we just skip straight to its blocks.
+                None => {
+                    self.section_iter_current =
+                        self.sections.next().map(|section| section.iter_blocks());
                 }
+            }
+        }
+    }
+}
 
-                self.section_index_current += 1;
-                self.section_iter_current = self
-                    .sections
-                    .get(self.section_index_current)
-                    .unwrap()
-                    .iter_blocks();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                self.section_iter_current.next()
-            }
-            Some(block) => Some(block),
-        };
+    /// A tower spanning section-Ys -1..3 with section-Y 1 deliberately
+    /// left unset, to exercise the missing-section behaviour directly
+    /// rather than via a full `From` conversion.
+    fn tower_with_gap() -> SectionTower {
+        let mut sections = BTreeMap::new();
+        sections.insert(-1, Section::empty());
+        sections.insert(0, Section::empty());
+        sections.insert(2, Section::empty());
+
+        SectionTower::for_test(sections, -16, 48)
+    }
+
+    #[test]
+    fn block_returns_none_for_missing_section_instead_of_panicking() {
+        let tower = tower_with_gap();
+
+        // y 16..32 falls in section-Y 1, which is absent.
+        assert_eq!(tower.block(0, 20, 0), None);
+    }
+
+    #[test]
+    fn biome_returns_none_for_missing_section_instead_of_panicking() {
+        let tower = tower_with_gap();
+
+        assert_eq!(tower.biome(0, 20, 0), None);
+    }
+
+    #[test]
+    fn light_returns_none_for_missing_section_instead_of_panicking() {
+        let tower = tower_with_gap();
+
+        assert_eq!(tower.light(0, 20, 0), None);
+    }
+
+    #[test]
+    fn iter_blocks_skips_missing_sections() {
+        let tower = tower_with_gap();
+
+        // Only the three present sections' blocks should be yielded; the
+        // missing section-Y 1 must not be treated as if it held air under
+        // some dense `0..len` index, and must not panic.
+        assert_eq!(tower.iter_blocks().count(), 3 * 16 * 16 * 16);
     }
 }
@@ -0,0 +1,285 @@
+//! Rebuilds the parts of a chunk's NBT that [`SectionTower::set_block`]
+//! leaves stale: each present section's `block_states` palette+data
+//! `LongArray`, and the chunk's heightmaps. Biomes are not written, so edits
+//! made with [`set_biome`][`SectionTower::set_biome`] are not carried into
+//! the output. The resulting NBT is handed to [`fastnbt::to_bytes`] the same
+//! way any other chunk is serialized; this module only rebuilds the parts
+//! that depend on the section/tower contents.
+//!
+//! [`write_chunk_to_region`] takes those bytes the rest of the way: it
+//! compresses them and writes them into a region file's sector space,
+//! updating the offset and timestamp tables the same way vanilla does.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use fastnbt::LongArray;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+use crate::complete::section_tower::SectionTower;
+use crate::lighting::opacity;
+use crate::Block;
+
+/// Bytes per region-file sector. Each of the two header tables (sector
+/// offsets, then timestamps) fills exactly one sector, 4 bytes per chunk.
+const SECTOR_SIZE: u64 = 4096;
+
+/// Number of header sectors (offset table + timestamp table) preceding the
+/// first chunk payload in a region file.
+const HEADER_SECTORS: u64 = 2;
+
+/// The chunk-payload compression scheme byte for zlib, per the Anvil format.
+const COMPRESSION_ZLIB: u8 = 2;
+
+/// Pack `indices` into the `LongArray` layout Minecraft uses for palette
+/// data: each entry takes `bits_per_entry` bits, packed low-bit-first, with
+/// entries never split across a `Long` boundary (the format used since
+/// 1.16; older saves padded the last entry of each long instead).
+pub fn pack_long_array(indices: &[u64], bits_per_entry: usize) -> Vec<i64> {
+    let entries_per_long = 64 / bits_per_entry;
+    let mask = (1u64 << bits_per_entry) - 1;
+
+    indices
+        .chunks(entries_per_long)
+        .map(|chunk| {
+            let mut long = 0u64;
+            for (slot, &index) in chunk.iter().enumerate() {
+                long |= (index & mask) << (slot * bits_per_entry);
+            }
+            long as i64
+        })
+        .collect()
+}
+
+/// Pack `indices` into the dense `LongArray` layout vanilla uses for
+/// heightmaps: unlike [`pack_long_array`], entries are split across a
+/// `Long` boundary whenever they don't divide it evenly. This is what
+/// actually yields 36 longs for a 256-entry, 9-bit heightmap (`256 * 9 =
+/// 2304 = 36 * 64` exactly); `pack_long_array` would waste the unused tail
+/// of each long and need 37.
+pub fn pack_long_array_dense(indices: &[u64], bits_per_entry: usize) -> Vec<i64> {
+    let total_bits = indices.len() * bits_per_entry;
+    let long_count = (total_bits + 63) / 64;
+    let mut longs = vec![0u64; long_count];
+    let mask = (1u64 << bits_per_entry) - 1;
+
+    for (i, &index) in indices.iter().enumerate() {
+        let bit_pos = i * bits_per_entry;
+        let long_index = bit_pos / 64;
+        let bit_offset = bit_pos % 64;
+        let value = (index & mask) << bit_offset;
+
+        longs[long_index] |= value;
+        if bit_offset + bits_per_entry > 64 {
+            longs[long_index + 1] |= value >> (64 - bit_offset);
+        }
+    }
+
+    longs.into_iter().map(|l| l as i64).collect()
+}
+
+/// The minimum bits-per-entry needed to index a palette of this size,
+/// following vanilla's own lower bound of 4 bits for block states.
+pub fn bits_for_palette_len(palette_len: usize) -> usize {
+    let needed = usize::BITS - (palette_len.saturating_sub(1)).leading_zeros();
+    (needed as usize).max(4)
+}
+
+/// Recompute the `MOTION_BLOCKING`-style heightmap for `tower`: for each of
+/// the 16x16 columns, the Y just above the highest block for which
+/// `is_opaque` returns true, relative to `tower.y_range().start` (0 for a
+/// column with no such block).
+///
+/// Returned as the packed 9-bit-per-entry `LongArray` vanilla stores
+/// heightmaps as (36 longs for a 256-entry heightmap).
+pub fn recompute_heightmap(tower: &SectionTower, is_opaque: impl Fn(&Block) -> bool) -> Vec<i64> {
+    let y_range = tower.y_range();
+    let mut heights = vec![0u64; 16 * 16];
+
+    for z in 0..16usize {
+        for x in 0..16usize {
+            let height = y_range
+                .clone()
+                .rev()
+                .find(|&y| tower.block(x, y, z).is_some_and(&is_opaque))
+                .map(|y| y - y_range.start + 1)
+                .unwrap_or(0);
+
+            heights[z * 16 + x] = height as u64;
+        }
+    }
+
+    pack_long_array_dense(&heights, 9)
+}
+
+/// Rebuild a section's `block_states` palette and packed indices from its
+/// actual block contents, the way vanilla does whenever it writes a chunk
+/// back out: each distinct block -- by its full state, not just its name,
+/// so e.g. stairs/logs/rails facing a different direction or redstone at a
+/// different power level each get their own entry -- becomes one palette
+/// entry, and every block position becomes an index into that palette.
+pub fn rebuild_block_states<'a>(blocks: impl Iterator<Item = &'a Block>) -> (Vec<Block>, Vec<i64>)
+where
+    Block: Clone + Eq + std::hash::Hash + 'a,
+{
+    let mut palette: Vec<Block> = Vec::new();
+    let mut index_of: HashMap<Block, u64> = HashMap::new();
+    let mut indices: Vec<u64> = Vec::new();
+
+    for block in blocks {
+        let index = *index_of.entry(block.clone()).or_insert_with(|| {
+            palette.push(block.clone());
+            (palette.len() - 1) as u64
+        });
+        indices.push(index);
+    }
+
+    let bits = bits_for_palette_len(palette.len().max(1));
+    (palette, pack_long_array(&indices, bits))
+}
+
+/// The on-disk shape of a section's `block_states` tag.
+#[derive(Serialize)]
+struct BlockStatesNbt {
+    palette: Vec<Block>,
+    /// Omitted when the palette has a single entry: vanilla leaves `data`
+    /// out entirely when there's nothing left for indices to distinguish.
+    data: Option<LongArray>,
+}
+
+#[derive(Serialize)]
+struct SectionNbt {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: BlockStatesNbt,
+}
+
+#[derive(Serialize)]
+struct HeightmapsNbt {
+    #[serde(rename = "MOTION_BLOCKING")]
+    motion_blocking: LongArray,
+}
+
+#[derive(Serialize)]
+struct ChunkNbt {
+    sections: Vec<SectionNbt>,
+    heightmaps: HeightmapsNbt,
+}
+
+fn rebuild_chunk_nbt(tower: &SectionTower) -> ChunkNbt
+where
+    Block: Clone + Eq + std::hash::Hash,
+{
+    let sections = tower
+        .sections()
+        .map(|(y, section)| {
+            let (palette, data) = rebuild_block_states(section.iter_blocks());
+            let data = (palette.len() > 1).then(|| LongArray::new(data));
+
+            SectionNbt {
+                y,
+                block_states: BlockStatesNbt { palette, data },
+            }
+        })
+        .collect();
+
+    let motion_blocking = recompute_heightmap(tower, |block| opacity(block) > 0);
+
+    ChunkNbt {
+        sections,
+        heightmaps: HeightmapsNbt {
+            motion_blocking: LongArray::new(motion_blocking),
+        },
+    }
+}
+
+/// Serialize `tower`'s current (possibly edited) contents as chunk NBT via
+/// [`fastnbt::to_bytes`]. The output holds only `sections` (each with its
+/// `Y` and `block_states`) and `heightmaps` (`MOTION_BLOCKING`); no other
+/// chunk tags, biomes or light arrays are included. Only produces the
+/// chunk's own bytes; see [`write_chunk_to_region`] for
+/// slotting them into a region file.
+pub fn write_chunk<W: Write>(writer: &mut W, tower: &SectionTower) -> io::Result<()>
+where
+    Block: Clone + Eq + std::hash::Hash,
+{
+    let nbt = rebuild_chunk_nbt(tower);
+    let bytes = fastnbt::to_bytes(&nbt).map_err(io::Error::other)?;
+    writer.write_all(&bytes)
+}
+
+/// Write `tower`'s current contents into the region file at `path`, at the
+/// chunk position `(chunk_x, chunk_z)` (region-relative, each `0..32`),
+/// stamping the timestamp table entry with `timestamp` (seconds since the
+/// epoch, as vanilla stores it).
+///
+/// Follows the Anvil region format directly: the chunk NBT is zlib-deflated
+/// and appended as a new run of whole 4096-byte sectors at the end of the
+/// file (the space the old payload occupied, if any, is simply abandoned --
+/// the same trade-off vanilla makes rather than compacting the file on
+/// every write), then the 4-byte big-endian `(offset, sector count)` and
+/// timestamp entries for this chunk are seeked to and overwritten. The file
+/// is created, with empty header sectors, if it doesn't already exist.
+pub fn write_chunk_to_region(
+    path: impl AsRef<Path>,
+    chunk_x: usize,
+    chunk_z: usize,
+    timestamp: u32,
+    tower: &SectionTower,
+) -> io::Result<()> {
+    assert!(chunk_x < 32 && chunk_z < 32);
+
+    let mut nbt = Vec::new();
+    write_chunk(&mut nbt, tower)?;
+
+    let mut payload = Vec::new();
+    payload.push(COMPRESSION_ZLIB);
+    let mut encoder = ZlibEncoder::new(&mut payload, Compression::default());
+    encoder.write_all(&nbt)?;
+    encoder.finish()?;
+
+    // The length field counts the bytes that follow it: the compression
+    // byte plus the compressed data, i.e. exactly `payload`.
+    let length = payload.len() as u32;
+    let mut entry = (length).to_be_bytes().to_vec();
+    entry.extend_from_slice(&payload);
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)?;
+
+    let header_len = HEADER_SECTORS * SECTOR_SIZE;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if file_len < header_len {
+        file.set_len(header_len)?;
+    }
+
+    let sector_count = (entry.len() as u64).div_ceil(SECTOR_SIZE);
+    let padded_len = sector_count * SECTOR_SIZE;
+
+    let write_offset = file.seek(SeekFrom::End(0))?.max(header_len);
+    let sector_offset = write_offset / SECTOR_SIZE;
+
+    file.seek(SeekFrom::Start(write_offset))?;
+    file.write_all(&entry)?;
+    file.write_all(&vec![0u8; (padded_len - entry.len() as u64) as usize])?;
+
+    let table_index = chunk_z * 32 + chunk_x;
+
+    let mut location = [0u8; 4];
+    location[0..3].copy_from_slice(&(sector_offset as u32).to_be_bytes()[1..4]);
+    location[3] = sector_count as u8;
+    file.seek(SeekFrom::Start(table_index as u64 * 4))?;
+    file.write_all(&location)?;
+
+    file.seek(SeekFrom::Start(SECTOR_SIZE + table_index as u64 * 4))?;
+    file.write_all(&timestamp.to_be_bytes())?;
+
+    Ok(())
+}
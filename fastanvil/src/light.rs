@@ -0,0 +1,135 @@
+//! Helpers for reading and applying per-block light levels.
+//!
+//! Minecraft sections store `BlockLight` and `SkyLight` as 2048-byte
+//! nibble-packed arrays: one 0-15 value per block, four bits each, two
+//! blocks per byte with the low nibble holding the first block.
+
+use crate::{Chunk, HeightMode};
+
+/// Selects which stored light channel (if any) drives map shading.
+///
+/// Mirrors [`HeightMode`][`crate::HeightMode`] in spirit: a simple switch
+/// the renderer consults rather than a value threaded through every call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightMode {
+    /// Shade using sky light only.
+    Sky,
+    /// Shade using block light only (torches, lava, glowstone, etc).
+    Block,
+    /// Shade using whichever of sky/block light is brighter at that block.
+    Max,
+    /// Don't factor light into shading at all.
+    Off,
+}
+
+/// Extract the nibble for `index` (0..4096) out of a packed 2048-byte light
+/// array, as stored in `BlockLight`/`SkyLight` NBT tags.
+pub(crate) fn nibble4(data: &[u8; 2048], index: usize) -> u8 {
+    let byte = data[index / 2];
+    if index % 2 == 0 {
+        byte & 0x0F
+    } else {
+        (byte >> 4) & 0x0F
+    }
+}
+
+/// Pack 4096 nibble values (0-15) back into a 2048-byte array, low nibble
+/// first, the inverse of [`nibble4`].
+pub(crate) fn pack_nibbles(values: &[u8; 4096]) -> [u8; 2048] {
+    let mut packed = [0u8; 2048];
+    for (index, &value) in values.iter().enumerate() {
+        let value = value & 0x0F;
+        if index % 2 == 0 {
+            packed[index / 2] |= value;
+        } else {
+            packed[index / 2] |= value << 4;
+        }
+    }
+    packed
+}
+
+/// A chunk that can report its own stored light levels.
+///
+/// This is kept separate from [`Chunk`] rather than added as a method on it,
+/// since `Chunk`'s definition is out of scope for this patch series and not
+/// every implementor stores light data. Types that do should implement this
+/// in addition to `Chunk`; callers that want light-aware rendering but don't
+/// have a `ChunkLight` implementor on hand can't get one for free -- there's
+/// no blanket impl, since that would foreclose any real implementor from
+/// ever providing genuine light data.
+pub trait ChunkLight {
+    /// The stored `(block light, sky light)` at this position, or `None` if
+    /// unavailable (section missing, or light not computed for it).
+    fn light(&self, x: usize, y: isize, z: usize) -> Option<(u8, u8)>;
+}
+
+/// Recompute sky light for a single block with a simple downward flood,
+/// for chunks that have no stored `SkyLight` array to fall back on.
+///
+/// This mirrors how game clients rebuild light: the column is seeded at
+/// full brightness down to (and including) the topmost solid block, and
+/// everything below that is assumed unlit. It does not attempt to flood
+/// through transparent blocks sitting below an opaque one (caves lit only
+/// by stored light will look dark), but keeps renders sensible for saves
+/// that never had light computed at all.
+pub fn fallback_sky_light<C: Chunk>(
+    x: usize,
+    y: isize,
+    z: usize,
+    chunk: &C,
+    height_mode: HeightMode,
+) -> u8 {
+    let air_height = chunk.surface_height(x, z, height_mode);
+    let top_solid = air_height - 1;
+
+    if y >= top_solid {
+        15
+    } else {
+        0
+    }
+}
+
+/// Convert a 0-15 light level into a brightness multiplier.
+///
+/// Minecraft's own light attenuation isn't linear -- each level down cuts
+/// brightness by a roughly constant fraction, so low light stays visibly
+/// dark without crushing everything below full brightness to black.
+pub fn light_shade(level: u8) -> f32 {
+    0.85f32.powi((15 - level.min(15)) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nibble4_reads_low_then_high_nibble() {
+        let mut data = [0u8; 2048];
+        data[0] = 0x5A; // low nibble 0xA (index 0), high nibble 0x5 (index 1)
+
+        assert_eq!(nibble4(&data, 0), 0xA);
+        assert_eq!(nibble4(&data, 1), 0x5);
+    }
+
+    #[test]
+    fn pack_nibbles_round_trips_through_nibble4() {
+        let mut values = [0u8; 4096];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = (index % 16) as u8;
+        }
+
+        let packed = pack_nibbles(&values);
+        for (index, &expected) in values.iter().enumerate() {
+            assert_eq!(nibble4(&packed, index), expected);
+        }
+    }
+
+    #[test]
+    fn pack_nibbles_masks_values_above_15() {
+        let mut values = [0u8; 4096];
+        values[0] = 0xFF;
+
+        let packed = pack_nibbles(&values);
+        assert_eq!(nibble4(&packed, 0), 0x0F);
+    }
+}
@@ -0,0 +1,262 @@
+//! Recomputes block and sky light from scratch over a [`SectionTower`],
+//! for worlds where the stored `BlockLight`/`SkyLight` arrays are absent
+//! or stale. Uses the same BFS flood-fill game clients use to rebuild
+//! lighting: light levels start at an emitter (or open sky) and spread
+//! outward, losing strength to distance and to each block's opacity,
+//! until no neighbour can be brightened any further.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::complete::section_tower::SectionTower;
+use crate::light::pack_nibbles;
+use crate::Block;
+
+/// Which light channel a propagation pass is computing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    /// Light emitted by blocks: torches, lava, glowstone, etc.
+    Block,
+    /// Light falling from open sky.
+    Sky,
+}
+
+/// How much light (0-15) a block emits into neighbouring blocks.
+pub fn luminance(block: &Block) -> u8 {
+    match block.name() {
+        "minecraft:lava"
+        | "minecraft:glowstone"
+        | "minecraft:sea_lantern"
+        | "minecraft:beacon"
+        | "minecraft:jack_o_lantern" => 15,
+        "minecraft:shroomlight" | "minecraft:end_rod" => 14,
+        "minecraft:torch" | "minecraft:wall_torch" => 14,
+        "minecraft:soul_torch" | "minecraft:soul_wall_torch" => 10,
+        "minecraft:redstone_torch" | "minecraft:redstone_wall_torch" => 7,
+        "minecraft:magma_block" => 3,
+        _ => 0,
+    }
+}
+
+/// How much a block attenuates light passing through it. Opaque blocks use
+/// 15, enough to fully terminate propagation since levels never go below
+/// zero. Transparent blocks (air, glass, flowing water) use 0-1.
+pub fn opacity(block: &Block) -> u8 {
+    match block.name() {
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air" => 0,
+        "minecraft:glass" | "minecraft:water" | "minecraft:ice" => 1,
+        _ => 15,
+    }
+}
+
+/// Recompute `kind` light across every section of `tower`, returning packed
+/// `SkyLight`/`BlockLight`-shaped nibble arrays keyed by section-Y, ready to
+/// write straight back into a chunk's NBT.
+pub fn compute_light(tower: &SectionTower, kind: LightKind) -> BTreeMap<i8, [u8; 2048]> {
+    LightEngine::new(tower, kind).run()
+}
+
+struct LightEngine<'a> {
+    tower: &'a SectionTower,
+    kind: LightKind,
+    levels: BTreeMap<(isize, isize, isize), u8>,
+    queue: VecDeque<(isize, isize, isize)>,
+}
+
+const NEIGHBOURS: [(isize, isize, isize); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+impl<'a> LightEngine<'a> {
+    fn new(tower: &'a SectionTower, kind: LightKind) -> Self {
+        Self {
+            tower,
+            kind,
+            levels: BTreeMap::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn run(mut self) -> BTreeMap<i8, [u8; 2048]> {
+        self.seed();
+
+        while let Some((x, y, z)) = self.queue.pop_front() {
+            self.propagate_from(x, y, z);
+        }
+
+        self.into_packed_sections()
+    }
+
+    fn seed(&mut self) {
+        let y_range = self.tower.y_range();
+
+        for z in 0..16isize {
+            for x in 0..16isize {
+                match self.kind {
+                    LightKind::Block => {
+                        for y in y_range.clone() {
+                            if let Some(block) = self.tower.block(x as usize, y, z as usize) {
+                                let level = luminance(block);
+                                if level > 0 {
+                                    self.raise(x, y, z, level);
+                                }
+                            }
+                        }
+                    }
+                    LightKind::Sky => {
+                        // Seed every block from the top of the tower down to
+                        // (but not including) the first block with any
+                        // opacity at all, glass and water included: those
+                        // are the ones actually exposed to open sky. A solid
+                        // roof -- a tall build reaching the height limit, or
+                        // a superflat/void-world ceiling -- stops the seed
+                        // immediately, so the no-decrement-straight-down
+                        // rule in `propagate_from` can't flood a false 15
+                        // into whatever's enclosed beneath it. A missing
+                        // section is treated as unseen rather than opaque,
+                        // the same as `SectionTower::block` does, so the
+                        // scan keeps looking below it.
+                        for y in y_range.clone().rev() {
+                            match self.tower.block(x as usize, y, z as usize) {
+                                Some(block) if opacity(block) == 0 => self.raise(x, y, z, 15),
+                                Some(_) => break,
+                                None => continue,
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn propagate_from(&mut self, x: isize, y: isize, z: isize) {
+        let current = *self.levels.get(&(x, y, z)).unwrap_or(&0);
+        if current == 0 {
+            return;
+        }
+
+        let y_range = self.tower.y_range();
+
+        for &(dx, dy, dz) in &NEIGHBOURS {
+            let (nx, nz) = (x + dx, z + dz);
+            if !(0..16).contains(&nx) || !(0..16).contains(&nz) {
+                continue;
+            }
+            let ny = y + dy;
+            if ny < y_range.start || ny >= y_range.end {
+                continue;
+            }
+
+            let neighbour = match self.tower.block(nx as usize, ny, nz as usize) {
+                Some(block) => block,
+                None => continue,
+            };
+            let neighbour_opacity = opacity(neighbour);
+
+            let new_level = if self.kind == LightKind::Sky && dy == -1 && neighbour_opacity == 0 {
+                current
+            } else {
+                current.saturating_sub(1 + neighbour_opacity)
+            };
+
+            if new_level > 0 {
+                self.raise(nx, ny, nz, new_level);
+            }
+        }
+    }
+
+    /// Record `level` at this position and re-enqueue it, but only if it's
+    /// brighter than what's already recorded -- this is what keeps the
+    /// flood-fill from revisiting settled nodes forever.
+    fn raise(&mut self, x: isize, y: isize, z: isize, level: u8) {
+        let level = level.min(15);
+        let existing = *self.levels.get(&(x, y, z)).unwrap_or(&0);
+        if level > existing {
+            self.levels.insert((x, y, z), level);
+            self.queue.push_back((x, y, z));
+        }
+    }
+
+    fn into_packed_sections(self) -> BTreeMap<i8, [u8; 2048]> {
+        let mut per_section: BTreeMap<i8, [u8; 4096]> = BTreeMap::new();
+
+        for ((x, y, z), level) in self.levels {
+            let section_y = y.div_euclid(16) as i8;
+            let local_y = y.rem_euclid(16) as usize;
+            let index = (local_y * 16 + z as usize) * 16 + x as usize;
+
+            per_section.entry(section_y).or_insert([0u8; 4096])[index] = level;
+        }
+
+        per_section
+            .into_iter()
+            .map(|(section_y, levels)| (section_y, pack_nibbles(&levels)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::complete::section::Section;
+
+    /// Two all-air sections (section-Y -1 and 0), spanning world Y -16..16.
+    fn air_tower() -> SectionTower {
+        let mut sections = BTreeMap::new();
+        sections.insert(-1, Section::empty());
+        sections.insert(0, Section::empty());
+
+        SectionTower::for_test(sections, -16, 16)
+    }
+
+    #[test]
+    fn opacity_and_luminance_of_air_are_both_zero() {
+        let tower = air_tower();
+        let air = tower.block(0, 0, 0).expect("empty section is all air");
+
+        assert_eq!(opacity(air), 0);
+        assert_eq!(luminance(air), 0);
+    }
+
+    #[test]
+    fn sky_light_floods_straight_down_through_air_without_decrementing() {
+        let levels = compute_light(&air_tower(), LightKind::Sky);
+
+        // Every column is open air top to bottom, so sky light should reach
+        // full brightness everywhere rather than decaying with depth.
+        for section in [-1i8, 0] {
+            let packed = &levels[&section];
+            for index in 0..4096 {
+                assert_eq!(
+                    crate::light::nibble4(packed, index),
+                    15,
+                    "section {section} index {index}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn block_light_seeds_nothing_without_a_luminant_block() {
+        let levels = compute_light(&air_tower(), LightKind::Block);
+
+        // No emitters anywhere in an all-air tower, so nothing should ever
+        // get enqueued and no section should show up in the result.
+        assert!(levels.is_empty());
+    }
+
+    #[test]
+    fn raise_clamps_levels_to_15() {
+        let tower = air_tower();
+        let mut engine = LightEngine::new(&tower, LightKind::Block);
+
+        engine.raise(0, 0, 0, 255);
+        assert_eq!(engine.levels[&(0, 0, 0)], 15);
+    }
+}
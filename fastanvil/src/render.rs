@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 
+use crate::light::{fallback_sky_light, light_shade, ChunkLight, LightMode};
 use crate::{Block, CCoord, Chunk, Dimension, HeightMode, RCoord};
 
 use super::biome::Biome;
@@ -15,17 +16,53 @@ pub trait Palette {
 pub struct TopShadeRenderer<'a, P: Palette> {
     palette: &'a P,
     height_mode: HeightMode,
+    light_mode: LightMode,
 }
 
 impl<'a, P: Palette> TopShadeRenderer<'a, P> {
     pub fn new(palette: &'a P, mode: HeightMode) -> Self {
+        Self::new_with_light(palette, mode, LightMode::Off)
+    }
+
+    /// As [`new`][`Self::new`], but also shading blocks by their light
+    /// level. See [`LightMode`] for the channels this can draw from. Stored
+    /// light is only read when rendering through [`render_with_light`]
+    /// [`Self::render_with_light`]; plain [`render`][`Self::render`] has no
+    /// stored light, so `Sky` and `Max` shade from
+    /// [`fallback_sky_light`][`crate::light::fallback_sky_light`] alone and
+    /// `Block` leaves colours unshaded.
+    pub fn new_with_light(palette: &'a P, height_mode: HeightMode, light_mode: LightMode) -> Self {
         Self {
             palette,
-            height_mode: mode,
+            height_mode,
+            light_mode,
         }
     }
 
     pub fn render<C: Chunk>(&self, chunk: &C, north: Option<&C>) -> [Rgba; 16 * 16] {
+        self.render_impl(chunk, north, &|_, _, _| None)
+    }
+
+    /// As [`render`][`Self::render`], but able to shade by the chunk's own
+    /// stored light levels (per [`new_with_light`][`Self::new_with_light`]).
+    /// Takes `C: ChunkLight` in addition to `C: Chunk`, since not every
+    /// chunk implementor carries light data -- callers that never configure
+    /// a [`LightMode`] other than `Off` can keep using plain `render` and
+    /// their chunk type doesn't need to implement `ChunkLight` at all.
+    pub fn render_with_light<C: Chunk + ChunkLight>(
+        &self,
+        chunk: &C,
+        north: Option<&C>,
+    ) -> [Rgba; 16 * 16] {
+        self.render_impl(chunk, north, &|x, y, z| chunk.light(x, y, z))
+    }
+
+    fn render_impl<C: Chunk>(
+        &self,
+        chunk: &C,
+        north: Option<&C>,
+        light: &dyn Fn(usize, isize, usize) -> Option<(u8, u8)>,
+    ) -> [Rgba; 16 * 16] {
         let mut data = [[0, 0, 0, 0]; 16 * 16];
 
         let y_range = chunk.y_range();
@@ -35,7 +72,7 @@ impl<'a, P: Palette> TopShadeRenderer<'a, P> {
                 let air_height = chunk.surface_height(x, z, self.height_mode);
                 let block_height = (air_height - 1).max(y_range.start);
 
-                let colour = self.drill_for_colour(x, block_height, z, chunk, y_range.start);
+                let colour = self.drill_for_colour(x, block_height, z, chunk, y_range.start, light);
 
                 let north_air_height = match z {
                     // if top of chunk, get height from the chunk above.
@@ -62,6 +99,7 @@ impl<'a, P: Palette> TopShadeRenderer<'a, P> {
         z: usize,
         chunk: &C,
         y_min: isize,
+        light: &dyn Fn(usize, isize, usize) -> Option<(u8, u8)>,
     ) -> Rgba {
         let mut y = y_start;
         let mut colour = [0, 0, 0, 0];
@@ -79,6 +117,7 @@ impl<'a, P: Palette> TopShadeRenderer<'a, P> {
                     // heightmaps.
                     block if is_watery(block) => {
                         let mut block_colour = self.palette.pick(current_block, current_biome);
+                        self.apply_light_shade(&mut block_colour, x, y, z, chunk, light);
                         let water_depth = water_depth(x, y, z, chunk, y_min);
                         let alpha = water_depth_to_alpha(water_depth);
 
@@ -88,7 +127,8 @@ impl<'a, P: Palette> TopShadeRenderer<'a, P> {
                         y -= water_depth;
                     }
                     _ => {
-                        let block_colour = self.palette.pick(current_block, current_biome);
+                        let mut block_colour = self.palette.pick(current_block, current_biome);
+                        self.apply_light_shade(&mut block_colour, x, y, z, chunk, light);
                         colour = a_over_b_colour(colour, block_colour);
                         y -= 1;
                     }
@@ -100,6 +140,42 @@ impl<'a, P: Palette> TopShadeRenderer<'a, P> {
 
         colour
     }
+
+    /// Darken `colour` in place by the light level at this
+    /// block, according to `self.light_mode`. A no-op when `LightMode::Off`.
+    /// `light` is whatever stored light `chunk.light(x, y, z)` returns, or
+    /// always `None` for callers that never implement `ChunkLight`.
+    fn apply_light_shade<C: Chunk>(
+        &self,
+        colour: &mut Rgba,
+        x: usize,
+        y: isize,
+        z: usize,
+        chunk: &C,
+        light: &dyn Fn(usize, isize, usize) -> Option<(u8, u8)>,
+    ) {
+        let level = match self.light_mode {
+            LightMode::Off => return,
+            LightMode::Sky => Some(
+                light(x, y, z)
+                    .map(|(_block, sky)| sky)
+                    .unwrap_or_else(|| fallback_sky_light(x, y, z, chunk, self.height_mode)),
+            ),
+            LightMode::Block => light(x, y, z).map(|(block, _sky)| block),
+            LightMode::Max => {
+                let (block, sky) = light(x, y, z)
+                    .unwrap_or((0, fallback_sky_light(x, y, z, chunk, self.height_mode)));
+                Some(block.max(sky))
+            }
+        };
+
+        if let Some(level) = level {
+            let shade = light_shade(level);
+            colour[0] = (colour[0] as f32 * shade) as u8;
+            colour[1] = (colour[1] as f32 * shade) as u8;
+            colour[2] = (colour[2] as f32 * shade) as u8;
+        }
+    }
 }
 
 /// Blocks that are considered as if they are water when determining colour.
@@ -228,6 +304,32 @@ pub fn render_region<P: Palette, C: Chunk + std::fmt::Debug>(
     z: RCoord,
     dimension: Dimension<C>,
     renderer: TopShadeRenderer<P>,
+) -> RegionMap<Rgba> {
+    render_region_impl(x, z, dimension, |chunk, north| {
+        renderer.render(chunk, north)
+    })
+}
+
+/// As [`render_region`], but shading by each chunk's own stored light (see
+/// [`TopShadeRenderer::render_with_light`]). Requires `C: ChunkLight` in
+/// addition to `C: Chunk`; callers that never need light-aware rendering
+/// can keep using plain `render_region` without that bound.
+pub fn render_region_with_light<P: Palette, C: Chunk + ChunkLight + std::fmt::Debug>(
+    x: RCoord,
+    z: RCoord,
+    dimension: Dimension<C>,
+    renderer: TopShadeRenderer<P>,
+) -> RegionMap<Rgba> {
+    render_region_impl(x, z, dimension, |chunk, north| {
+        renderer.render_with_light(chunk, north)
+    })
+}
+
+fn render_region_impl<C: Chunk + std::fmt::Debug>(
+    x: RCoord,
+    z: RCoord,
+    dimension: Dimension<C>,
+    render: impl Fn(&C, Option<&C>) -> [Rgba; 16 * 16],
 ) -> RegionMap<Rgba> {
     let mut map = RegionMap::new(x, z, [0u8; 4]);
 
@@ -262,7 +364,7 @@ pub fn render_region<P: Palette, C: Chunk + std::fmt::Debug>(
                 // first row or for any missing chunks.
                 let north = cache[x.0 as usize].as_ref();
 
-                let res = renderer.render(&chunk, north);
+                let res = render(&chunk, north);
                 cache[x.0 as usize] = Some(chunk);
                 res
             });